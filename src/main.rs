@@ -6,10 +6,12 @@
 #![deny(elided_lifetimes_in_paths)]
 #![deny(missing_debug_implementations)]
 
-use beancount_core::{Account, Amount, Flag, IncompleteAmount, Posting, Transaction};
+use beancount_core::{Account, Amount, Balance, Flag, IncompleteAmount, Posting, Price, Transaction};
 use beancount_render::{BasicRenderer, Renderer};
-use chrono::NaiveDate;
-use handlebars::Handlebars;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime};
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderErrorReason,
+};
 use rust_decimal::Decimal;
 use serde::Deserialize;
 use std::borrow::Cow;
@@ -28,10 +30,41 @@ enum TransactionError {
     InvalidAccount,
     #[error("could not render template")]
     HandleBarError(#[from] handlebars::TemplateRenderError),
-    #[error("invalid amount")]
-    InvalidAmount,
-    #[error("could not parse date")]
+    #[error("invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("could not parse date: {0}")]
     DateParseError(#[from] chrono::format::ParseError),
+    #[error("utc_offset_seconds value {0} is out of range")]
+    InvalidUtcOffset(i32),
+    #[error("date_format must list at least one format")]
+    EmptyDateFormat,
+    #[error("no rule matched the record and no default output was configured")]
+    NoMatchingRule,
+    #[error("unknown column: {0}")]
+    UnknownColumn(String),
+    #[error("column index {0} out of range for record")]
+    ColumnOutOfRange(usize),
+}
+
+/// An error that can occur when selecting a configuration from a `ConfigSet`.
+#[derive(Debug, Error)]
+enum ConfigSetError {
+    #[error("no configuration fragment's path matched {0}")]
+    NoMatchingFragment(PathBuf),
+    #[error("fragment matching {0} is missing required field `{1}`")]
+    MissingField(PathBuf, &'static str),
+    #[error("invalid glob pattern: {0}")]
+    Glob(#[from] glob::PatternError),
+}
+
+/// An error in a `Settings` value's invariants, checked once the configuration is loaded rather
+/// than each time a record is parsed.
+#[derive(Debug, Error)]
+enum SettingsError {
+    #[error("date_format must list at least one format")]
+    EmptyDateFormat,
+    #[error("utc_offset_seconds value {0} is out of range")]
+    InvalidUtcOffset(i32),
 }
 
 /// Any error that can occur in the application.
@@ -45,6 +78,10 @@ enum Error {
     Csv(#[from] csv::Error),
     #[error("could not parse the yaml")]
     Yaml(#[from] serde_yaml::Error),
+    #[error("could not select a configuration")]
+    ConfigSet(#[from] ConfigSetError),
+    #[error("invalid settings")]
+    Settings(#[from] SettingsError),
 }
 
 /// A tool to convert csv to beancount files.
@@ -71,13 +108,171 @@ struct Opt {
 /// The configuration used to convert the ledger entries.
 #[derive(Debug, Deserialize)]
 struct Configuration {
-    /// The keyed inputs from the csv.
-    input: HashMap<String, usize>,
+    /// The keyed inputs from the csv, either by column index or (when `settings.header` is set) by column name.
+    input: HashMap<String, ColumnRef>,
     /// The settings for the Yaml.
     settings: Settings,
+    /// The template used when no rule matches (or when there are no rules at all).
+    output: Option<TransactionTemplate>,
+    /// Rules evaluated in order to pick a template per record, before falling back to `output`.
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+/// A condition matched against a rendered input field, paired with the template to use when it matches.
+#[derive(Debug, Clone, Deserialize)]
+struct Rule {
+    /// The condition that selects this rule.
+    matcher: RuleMatcher,
+    /// The template to use when `matcher` matches the record.
     output: TransactionTemplate,
 }
 
+/// Matches a named input field against a plain substring (not a regular expression, despite the
+/// similarity to pattern-matching config elsewhere in this crate, e.g. `ConfigFragment::path`).
+#[derive(Debug, Clone, Deserialize)]
+struct RuleMatcher {
+    /// The name of the field in `Configuration::input` to test.
+    field: String,
+    /// The substring that must occur in the rendered field for this rule to match. Matched
+    /// literally: `"^DEP"` looks for that literal caret-prefixed text, it is not a regex anchor.
+    contains: String,
+}
+
+/// Select the template to use for a record's rendered `data`, trying `rules` in order before
+/// falling back to the top-level `output`.
+fn select_template<'a>(
+    config: &'a Configuration,
+    data: &HashMap<&str, &str>,
+) -> Result<&'a TransactionTemplate, TransactionError> {
+    for rule in &config.rules {
+        if let Some(value) = data.get(rule.matcher.field.as_str()) {
+            if value.contains(&rule.matcher.contains) {
+                return Ok(&rule.output);
+            }
+        }
+    }
+    config.output.as_ref().ok_or(TransactionError::NoMatchingRule)
+}
+
+/// A reference to a csv column, either by position or (when headers are enabled) by name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ColumnRef {
+    /// A zero-based column index.
+    Index(usize),
+    /// A column name, resolved against the csv's header row.
+    Name(String),
+}
+
+impl ColumnRef {
+    /// Resolve this reference to a column index, looking up names in `headers` when needed.
+    fn resolve(&self, headers: Option<&csv::StringRecord>) -> Result<usize, TransactionError> {
+        match self {
+            ColumnRef::Index(index) => Ok(*index),
+            ColumnRef::Name(name) => headers
+                .and_then(|headers| headers.iter().position(|field| field == name))
+                .ok_or_else(|| TransactionError::UnknownColumn(name.clone())),
+        }
+    }
+}
+
+/// A configuration file as read from disk: either a single `Configuration`, or a `ConfigSet`
+/// describing several bank formats at once.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ConfigFile {
+    /// Several fragments, selected by the path of the ledger being converted.
+    Set(ConfigSet),
+    /// A single configuration, applied regardless of the ledger's path.
+    Single(Box<Configuration>),
+}
+
+/// A set of configuration fragments, one of which is selected based on the `--ledger` path.
+#[derive(Debug, Deserialize)]
+struct ConfigSet {
+    /// The fragments to choose from.
+    entries: Vec<ConfigFragment>,
+}
+
+/// One fragment of a `ConfigSet`. A fragment without a `path` is a shared base whose fields are
+/// merged into whichever fragment is selected, so common settings need not be repeated.
+#[derive(Debug, Deserialize)]
+struct ConfigFragment {
+    /// A substring or glob (containing `*`) matched against the `--ledger` path. `None` marks the
+    /// shared base fragment.
+    path: Option<String>,
+    /// The keyed inputs from the csv, either by column index or (when `settings.header` is set) by column name.
+    #[serde(default)]
+    input: HashMap<String, ColumnRef>,
+    /// The settings for the Yaml.
+    settings: Option<Settings>,
+    /// The template used when no rule matches (or when there are no rules at all).
+    output: Option<TransactionTemplate>,
+    /// Rules evaluated in order to pick a template per record, before falling back to `output`.
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+impl ConfigSet {
+    /// Select the fragment whose `path` best matches `ledger_path` (longest matching pattern
+    /// wins), merge the shared base fragment (if any) into it, and build a `Configuration`.
+    fn select(&self, ledger_path: &std::path::Path) -> Result<Configuration, ConfigSetError> {
+        let path_str = ledger_path.to_string_lossy();
+        let base = self.entries.iter().find(|entry| entry.path.is_none());
+
+        let selected = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let pattern = entry.path.as_ref()?;
+                Some(fragment_matches(pattern, &path_str).map(|matches| (matches, pattern.len(), entry)))
+            })
+            .collect::<Result<Vec<_>, glob::PatternError>>()?
+            .into_iter()
+            .filter(|(matches, _, _)| *matches)
+            .max_by_key(|(_, len, _)| *len)
+            .map(|(_, _, entry)| entry)
+            .ok_or_else(|| ConfigSetError::NoMatchingFragment(ledger_path.to_path_buf()))?;
+
+        let mut input = base.map(|base| base.input.clone()).unwrap_or_default();
+        input.extend(selected.input.clone());
+
+        let settings = selected
+            .settings
+            .clone()
+            .or_else(|| base.and_then(|base| base.settings.clone()))
+            .ok_or_else(|| ConfigSetError::MissingField(ledger_path.to_path_buf(), "settings"))?;
+
+        let output = selected
+            .output
+            .clone()
+            .or_else(|| base.and_then(|base| base.output.clone()));
+
+        // `selected`'s own rules take priority over the shared base's, since `select_template`
+        // uses the first matching rule and a fragment's rules should be able to override the
+        // generic ones from the base.
+        let mut rules = selected.rules.clone();
+        rules.extend(base.map(|base| base.rules.clone()).unwrap_or_default());
+
+        Ok(Configuration {
+            input,
+            settings,
+            output,
+            rules,
+        })
+    }
+}
+
+/// Test whether `pattern` selects `path`: a glob (if it contains `*`) or a plain substring otherwise.
+fn fragment_matches(pattern: &str, path: &str) -> Result<bool, glob::PatternError> {
+    if pattern.contains('*') {
+        Ok(glob::Pattern::new(pattern)?.matches(path))
+    } else {
+        Ok(path.contains(pattern))
+    }
+}
+
 const fn default_delimiter() -> char {
     ','
 }
@@ -86,8 +281,12 @@ const fn default_quote() -> char {
     '\''
 }
 
+const fn default_decimal_separator() -> char {
+    '.'
+}
+
 /// Settings for the yaml file.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct Settings {
     #[serde(default = "default_delimiter")]
     delimiter: char,
@@ -95,23 +294,94 @@ struct Settings {
     quote: char,
     #[serde(default)]
     skip: usize,
-    date_format: String,
+    /// One or more `chrono` date (or, with `datetime`, timestamp) formats, tried in order until
+    /// one parses. Useful for statements whose date column format drifts between rows.
+    date_format: DateFormats,
+    /// Whether the csv's first row contains column headers, allowing `input` to use column names.
+    #[serde(default)]
+    header: bool,
+    /// Whether to trim leading/trailing whitespace from every field.
+    #[serde(default)]
+    trim: bool,
+    /// Whether to allow records with a varying number of fields.
+    #[serde(default)]
+    flexible: bool,
+    /// The character used as the decimal point in amount columns.
+    #[serde(default = "default_decimal_separator")]
+    decimal_separator: char,
+    /// An optional grouping separator to strip from amount columns, e.g. `,` for "1,234.56" or
+    /// `.` for the European "1.234,56" convention.
+    thousands_separator: Option<char>,
+    /// Whether `date_format` describes a full timestamp (optionally with a UTC offset, e.g.
+    /// `"%m/%d/%Y, %I:%M:%S %p"`) rather than a bare date. A parsed timestamp that carries a UTC
+    /// offset is converted to `utc_offset_seconds` (if set) before being truncated to the ledger
+    /// date; a timestamp without an offset is truncated unconverted.
+    #[serde(default)]
+    datetime: bool,
+    /// A UTC offset in seconds to convert offset-carrying timestamps to before truncating to a
+    /// date. Only used when `datetime` is set.
+    utc_offset_seconds: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Settings {
+    /// Check invariants that the derived `Deserialize` impl can't express: `date_format` must
+    /// list at least one format, and `utc_offset_seconds` (if set) must be a valid `FixedOffset`.
+    /// Called once when the configuration is loaded, so a bad config is rejected up front rather
+    /// than part-way through converting a ledger.
+    fn validate(&self) -> Result<(), SettingsError> {
+        if self.date_format.iter().next().is_none() {
+            return Err(SettingsError::EmptyDateFormat);
+        }
+        if let Some(seconds) = self.utc_offset_seconds {
+            if FixedOffset::east_opt(seconds).is_none() {
+                return Err(SettingsError::InvalidUtcOffset(seconds));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One or more date formats to try in order, for statements whose date column format drifts.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum DateFormats {
+    /// A single format used for every row.
+    Single(String),
+    /// Several formats, tried in order.
+    Multiple(Vec<String>),
+}
+
+impl DateFormats {
+    fn iter(&self) -> std::vec::IntoIter<&str> {
+        match self {
+            DateFormats::Single(format) => vec![format.as_str()],
+            DateFormats::Multiple(formats) => formats.iter().map(String::as_str).collect(),
+        }
+        .into_iter()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 struct YamlPosting {
     flag: Option<String>,
     account: String,
     amount: Option<String>,
     cost: Option<String>,
     price: Option<String>,
+    /// Account to use instead of `account` when the rendered `amount` is positive.
+    when_positive: Option<String>,
+    /// Account to use instead of `account` when the rendered `amount` is negative.
+    when_negative: Option<String>,
+    /// Flip the sign of the rendered `amount`, for a balancing posting driven by the same column.
+    #[serde(default)]
+    negate: bool,
 }
 
 fn default_transaction_flag() -> String {
     "!".into()
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct TransactionTemplate {
     date: String,
     #[serde(default = "default_transaction_flag")]
@@ -119,21 +389,73 @@ struct TransactionTemplate {
     payee: Option<String>,
     narration: String,
     postings: Vec<YamlPosting>,
+    /// An optional balance assertion, emitted alongside the transaction.
+    balance: Option<BalanceTemplate>,
+    /// An optional price directive, emitted alongside the transaction.
+    price: Option<PriceTemplate>,
 }
 
-/// Generate an `IncompleteAmount` from a string in the format "{{amount}} {{currency}}".
-fn incomplete_amount_from_string(s: String) -> Result<IncompleteAmount<'static>, TransactionError> {
-    let mut split = s.split(' ');
-    let value = split
-        .next()
-        .ok_or(TransactionError::InvalidAmount)?
-        .replace(',', ".")
+/// Template for a `balance` directive derived from the same record as a transaction.
+#[derive(Debug, Clone, Deserialize)]
+struct BalanceTemplate {
+    /// The account the balance is asserted for.
+    account: String,
+    /// The asserted amount, in the format "{{amount}} {{currency}}". The directive is skipped
+    /// for a record where this renders to an empty string, e.g. a statement's non-balance rows.
+    amount: String,
+    /// The date of the assertion. Defaults to the transaction's `date` template when omitted.
+    date: Option<String>,
+}
+
+/// Template for a `price` directive derived from the same record as a transaction.
+#[derive(Debug, Clone, Deserialize)]
+struct PriceTemplate {
+    /// The commodity this price is quoted for.
+    commodity: String,
+    /// The price, in the format "{{amount}} {{currency}}".
+    amount: String,
+    /// The date of the price. Defaults to the transaction's `date` template when omitted.
+    date: Option<String>,
+}
+
+/// Parse a string in the format "{{amount}} {{currency}}" into its decimal value and currency,
+/// using `settings` to interpret the decimal point and (optional) thousands separator and to
+/// tolerate a leading currency symbol.
+fn parse_decimal_and_currency(
+    s: &str,
+    settings: &Settings,
+) -> Result<(Decimal, String), TransactionError> {
+    let tokens: Vec<&str> = s.split_whitespace().collect();
+    let (currency, raw_value) = tokens
+        .split_last()
+        .map(|(currency, rest)| (currency.to_string(), rest.concat()))
+        .ok_or_else(|| TransactionError::InvalidAmount(s.to_string()))?;
+
+    let numeric = raw_value.trim_start_matches(|c: char| {
+        !c.is_ascii_digit()
+            && c != '-'
+            && Some(c) != settings.thousands_separator
+            && c != settings.decimal_separator
+    });
+
+    let normalized: String = numeric
+        .chars()
+        .filter(|&c| Some(c) != settings.thousands_separator)
+        .map(|c| if c == settings.decimal_separator { '.' } else { c })
+        .collect();
+
+    let value = normalized
         .parse::<Decimal>()
-        .map_err(|_| TransactionError::InvalidAmount)?;
-    let currency = split
-        .next()
-        .ok_or(TransactionError::InvalidAmount)?
-        .to_string();
+        .map_err(|_| TransactionError::InvalidAmount(s.to_string()))?;
+    Ok((value, currency))
+}
+
+/// Generate an `IncompleteAmount` from a string in the format "{{amount}} {{currency}}".
+fn incomplete_amount_from_string(
+    s: String,
+    settings: &Settings,
+) -> Result<IncompleteAmount<'static>, TransactionError> {
+    let (value, currency) = parse_decimal_and_currency(&s, settings)?;
     Ok(Amount::builder()
         .num(value)
         .currency(Cow::from(currency))
@@ -141,6 +463,87 @@ fn incomplete_amount_from_string(s: String) -> Result<IncompleteAmount<'static>,
         .into())
 }
 
+/// Parse handlebars parameter `index` of `helper` as a `Decimal`. Values taken from the record are
+/// rendered csv fields (see `data: &HashMap<&str, &str>` in `build_transaction`) and arrive as
+/// strings, so helpers parse strings rather than use `handlebars_helper!`'s numeric coercion, and
+/// parse the same type the rest of the crate uses for money. Numeric literals written directly in
+/// a template are accepted as well.
+fn decimal_param(helper: &Helper<'_>, index: usize) -> Result<Decimal, handlebars::RenderError> {
+    let value = helper
+        .param(index)
+        .map(|param| param.value())
+        .ok_or_else(|| RenderErrorReason::ParamNotFoundForIndex("amount helper", index))?;
+    // A param rendered from the crate's own `data` map always arrives as a string, but a literal
+    // written directly in a template (e.g. the `0` in `{{#if (gt amount 0)}}`) is parsed by
+    // handlebars as a JSON number instead, so both need to be accepted here.
+    let raw = value
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| value.is_number().then(|| value.to_string()))
+        .ok_or_else(|| {
+            RenderErrorReason::Other(format!(
+                "amount helper expects a string or number, got {value}"
+            ))
+        })?;
+    raw.trim()
+        .parse::<Decimal>()
+        .map_err(|_| RenderErrorReason::Other(format!("not a valid decimal: {raw}")).into())
+}
+
+fn abs_helper(
+    helper: &Helper<'_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&decimal_param(helper, 0)?.abs().to_string())?;
+    Ok(())
+}
+
+fn neg_helper(
+    helper: &Helper<'_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&(-decimal_param(helper, 0)?).to_string())?;
+    Ok(())
+}
+
+fn gt_helper(
+    helper: &Helper<'_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let result = decimal_param(helper, 0)? > decimal_param(helper, 1)?;
+    out.write(if result { "true" } else { "false" })?;
+    Ok(())
+}
+
+fn lt_helper(
+    helper: &Helper<'_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let result = decimal_param(helper, 0)? < decimal_param(helper, 1)?;
+    out.write(if result { "true" } else { "false" })?;
+    Ok(())
+}
+
+/// Register the handlebars helpers available to amount templates: `abs`, `neg`, `gt` and `lt`.
+fn register_amount_helpers(handlebars: &mut Handlebars<'_>) {
+    handlebars.register_helper("abs", Box::new(abs_helper));
+    handlebars.register_helper("neg", Box::new(neg_helper));
+    handlebars.register_helper("gt", Box::new(gt_helper));
+    handlebars.register_helper("lt", Box::new(lt_helper));
+}
+
 fn account_from_string(s: String) -> Result<Account<'static>, TransactionError> {
     let mut parts = s.split(':');
     use beancount_core::account_types::AccountType::*;
@@ -160,16 +563,40 @@ fn build_posting<'a>(
     posting_template: &'a YamlPosting,
     handlebars: &Handlebars<'_>,
     data: &HashMap<&str, &str>,
+    settings: &Settings,
 ) -> Result<Posting<'a>, TransactionError> {
-    let account =
-        account_from_string(handlebars.render_template(&posting_template.account, &data)?)?;
-    let units = posting_template
+    let rendered_amount = posting_template
         .amount
         .as_ref()
-        .map(|cost| handlebars.render_template(&cost, &data))
-        .transpose()?
-        .map(incomplete_amount_from_string)
-        .transpose()?
+        .map(|amount| handlebars.render_template(amount, &data))
+        .transpose()?;
+    let parsed_amount = rendered_amount
+        .as_deref()
+        .map(|amount| parse_decimal_and_currency(amount, settings))
+        .transpose()?;
+
+    let account_template = match &parsed_amount {
+        Some((value, _)) if value.is_sign_positive() => posting_template
+            .when_positive
+            .as_ref()
+            .unwrap_or(&posting_template.account),
+        Some((value, _)) if value.is_sign_negative() => posting_template
+            .when_negative
+            .as_ref()
+            .unwrap_or(&posting_template.account),
+        _ => &posting_template.account,
+    };
+    let account = account_from_string(handlebars.render_template(account_template, &data)?)?;
+
+    let units = parsed_amount
+        .map(|(value, currency)| {
+            let value = if posting_template.negate { -value } else { value };
+            Amount::builder()
+                .num(value)
+                .currency(Cow::from(currency))
+                .build()
+                .into()
+        })
         .unwrap_or_else(|| IncompleteAmount::builder().build());
     let flag = posting_template
         .flag
@@ -183,7 +610,7 @@ fn build_posting<'a>(
         .as_ref()
         .map(|price| handlebars.render_template(&price, &data))
         .transpose()?
-        .map(incomplete_amount_from_string)
+        .map(|price| incomplete_amount_from_string(price, settings))
         .transpose()?;
 
     Ok(Posting::builder()
@@ -194,24 +621,152 @@ fn build_posting<'a>(
         .build())
 }
 
+/// Build a `balance` directive from `template`, or `None` if its rendered `amount` is empty.
+fn build_balance<'a>(
+    template: &'a BalanceTemplate,
+    transaction_date_template: &'a str,
+    handlebars: &Handlebars<'_>,
+    data: &HashMap<&str, &str>,
+    settings: &Settings,
+) -> Result<Option<Balance<'a>>, TransactionError> {
+    let rendered_amount = handlebars.render_template(&template.amount, &data)?;
+    if rendered_amount.trim().is_empty() {
+        return Ok(None);
+    }
+    let (value, currency) = parse_decimal_and_currency(&rendered_amount, settings)?;
+
+    let date_template = template.date.as_deref().unwrap_or(transaction_date_template);
+    let date = parse_ledger_date(&handlebars.render_template(date_template, &data)?, settings)?;
+
+    let account = account_from_string(handlebars.render_template(&template.account, &data)?)?;
+    let amount = Amount::builder()
+        .num(value)
+        .currency(Cow::from(currency))
+        .build();
+
+    Ok(Some(
+        Balance::builder()
+            .date(date.into())
+            .account(account)
+            .amount(amount)
+            .build(),
+    ))
+}
+
+/// Build a `price` directive from `template`, or `None` if its rendered `amount` is empty (e.g.
+/// an exchange-rate column that's only populated on some rows).
+fn build_price<'a>(
+    template: &'a PriceTemplate,
+    transaction_date_template: &'a str,
+    handlebars: &Handlebars<'_>,
+    data: &HashMap<&str, &str>,
+    settings: &Settings,
+) -> Result<Option<Price<'a>>, TransactionError> {
+    let rendered_amount = handlebars.render_template(&template.amount, &data)?;
+    if rendered_amount.trim().is_empty() {
+        return Ok(None);
+    }
+    let (value, currency) = parse_decimal_and_currency(&rendered_amount, settings)?;
+
+    let date_template = template.date.as_deref().unwrap_or(transaction_date_template);
+    let date = parse_ledger_date(&handlebars.render_template(date_template, &data)?, settings)?;
+
+    let commodity = Cow::from(handlebars.render_template(&template.commodity, &data)?);
+    let amount = Amount::builder()
+        .num(value)
+        .currency(Cow::from(currency))
+        .build();
+
+    Ok(Some(
+        Price::builder()
+            .date(date.into())
+            .currency(commodity)
+            .amount(amount)
+            .build(),
+    ))
+}
+
+/// The directives derived from a single csv record: the transaction itself plus any optional
+/// balance assertion and price directive from its template.
+#[derive(Debug)]
+struct RecordDirectives<'a> {
+    transaction: Transaction<'a>,
+    balance: Option<Balance<'a>>,
+    price: Option<Price<'a>>,
+}
+
+/// Parse a single date column against one `format`, either as a bare date or, when `datetime` is
+/// set, as a full timestamp truncated to a date. A timestamp that carries a UTC offset is first
+/// converted to `utc_offset_seconds` (if set); one without an offset is truncated as it is, with
+/// no conversion.
+fn parse_date_with_format(
+    rendered: &str,
+    format: &str,
+    datetime: bool,
+    utc_offset_seconds: Option<i32>,
+) -> Result<NaiveDate, TransactionError> {
+    if !datetime {
+        return Ok(NaiveDate::parse_from_str(rendered, format)?);
+    }
+    // Try the offset-aware parse first: if `format` contains a timezone directive like `%z`,
+    // `NaiveDateTime::parse_from_str` would happily match it too but silently discard the parsed
+    // offset, so trying that first would skip the `utc_offset_seconds` conversion entirely.
+    match DateTime::<FixedOffset>::parse_from_str(rendered, format) {
+        Ok(with_offset) => {
+            let with_offset = match utc_offset_seconds {
+                Some(seconds) => with_offset.with_timezone(
+                    &FixedOffset::east_opt(seconds)
+                        .ok_or(TransactionError::InvalidUtcOffset(seconds))?,
+                ),
+                None => with_offset,
+            };
+            Ok(with_offset.naive_local().date())
+        }
+        Err(_) => Ok(NaiveDateTime::parse_from_str(rendered, format)?.date()),
+    }
+}
+
+/// Try each of `settings.date_format`'s formats in turn, returning the ledger date for the first
+/// one that parses `rendered`. Surfaces the last format's error when none of them do, or
+/// `TransactionError::EmptyDateFormat` if `date_format` has no formats at all (normally already
+/// rejected by `Settings::validate` when the configuration was loaded).
+fn parse_ledger_date(rendered: &str, settings: &Settings) -> Result<NaiveDate, TransactionError> {
+    let mut last_error = None;
+    for format in settings.date_format.iter() {
+        match parse_date_with_format(rendered, format, settings.datetime, settings.utc_offset_seconds) {
+            Ok(date) => return Ok(date),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.unwrap_or(TransactionError::EmptyDateFormat))
+}
+
 fn build_transaction<'a>(
     record: csv::StringRecord,
     config: &'a Configuration,
     handlebars: &Handlebars<'_>,
-) -> Result<Transaction<'a>, TransactionError> {
+    headers: Option<&csv::StringRecord>,
+) -> Result<RecordDirectives<'a>, TransactionError> {
     let data: HashMap<&str, &str> = config
         .input
         .iter()
-        .map(|(key, value)| -> (&str, &str) { (key, &record[*value]) })
-        .collect();
+        .map(|(key, column)| -> Result<(&str, &str), TransactionError> {
+            let index = column.resolve(headers)?;
+            let field = record
+                .get(index)
+                .ok_or(TransactionError::ColumnOutOfRange(index))?;
+            Ok((key.as_str(), field))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let template = select_template(config, &data)?;
 
-    let date = NaiveDate::parse_from_str(
-        &handlebars.render_template(&config.output.date, &data)?,
-        &config.settings.date_format,
+    let date = parse_ledger_date(
+        &handlebars.render_template(&template.date, &data)?,
+        &config.settings,
     )?;
 
-    let payee = config
-        .output
+    let payee = template
         .payee
         .as_ref()
         .map(|payee_template| handlebars.render_template(&payee_template, &data))
@@ -219,44 +774,101 @@ fn build_transaction<'a>(
         .filter(|payee| !payee.is_empty())
         .map(Cow::from);
 
-    let flag = Flag::from(handlebars.render_template(&config.output.flag, &data)?);
+    let flag = Flag::from(handlebars.render_template(&template.flag, &data)?);
 
-    let narration = handlebars.render_template(&config.output.narration, &data)?;
+    let narration = handlebars.render_template(&template.narration, &data)?;
 
-    let postings: Vec<Posting<'_>> = config
-        .output
+    let postings: Vec<Posting<'_>> = template
         .postings
         .iter()
-        .map(|posting_template: &YamlPosting| build_posting(posting_template, handlebars, &data))
+        .map(|posting_template: &YamlPosting| {
+            build_posting(posting_template, handlebars, &data, &config.settings)
+        })
         .collect::<Result<Vec<Posting<'_>>, TransactionError>>()?;
 
-    Ok(Transaction::builder()
+    let balance = template
+        .balance
+        .as_ref()
+        .map(|balance_template| {
+            build_balance(
+                balance_template,
+                &template.date,
+                handlebars,
+                &data,
+                &config.settings,
+            )
+        })
+        .transpose()?
+        .flatten();
+
+    let price = template
+        .price
+        .as_ref()
+        .map(|price_template| {
+            build_price(
+                price_template,
+                &template.date,
+                handlebars,
+                &data,
+                &config.settings,
+            )
+        })
+        .transpose()?
+        .flatten();
+
+    let transaction = Transaction::builder()
         .date(date.into())
         .flag(flag)
         .payee(payee)
         .narration(narration.into())
         .postings(postings)
-        .build())
+        .build();
+
+    Ok(RecordDirectives {
+        transaction,
+        balance,
+        price,
+    })
 }
 
 fn main() -> Result<(), anyhow::Error> {
     let opt = Opt::from_args();
 
-    let config: Configuration = {
+    let config_file: ConfigFile = {
         let yaml_file = std::fs::File::open(&opt.yaml_path)?;
         serde_yaml::from_reader(yaml_file)?
     };
+    let config: Configuration = match config_file {
+        ConfigFile::Single(config) => *config,
+        ConfigFile::Set(config_set) => config_set
+            .select(&opt.csv_path)
+            .map_err(Error::ConfigSet)?,
+    };
+    config.settings.validate().map_err(Error::Settings)?;
 
-    let csv_file = std::fs::File::open(opt.csv_path)?;
+    let csv_file = std::fs::File::open(&opt.csv_path)?;
 
     let mut rdr = csv::ReaderBuilder::new()
         .delimiter(config.settings.delimiter as u8)
         .quote(config.settings.quote as u8)
-        .has_headers(false)
+        .has_headers(config.settings.header)
+        .trim(if config.settings.trim {
+            csv::Trim::All
+        } else {
+            csv::Trim::None
+        })
+        .flexible(config.settings.flexible)
         .from_reader(csv_file);
 
+    let headers = if config.settings.header {
+        Some(rdr.headers()?.clone())
+    } else {
+        None
+    };
+
     let mut handlebars = Handlebars::new();
     handlebars.register_escape_fn(handlebars::no_escape);
+    register_amount_helpers(&mut handlebars);
 
     let mut write: Box<dyn io::Write> = if let Some(append_path) = opt.append_path {
         let file = OpenOptions::new().append(true).open(append_path)?;
@@ -267,8 +879,545 @@ fn main() -> Result<(), anyhow::Error> {
 
     let renderer = BasicRenderer::default();
     for record in rdr.records().skip(config.settings.skip) {
-        let transaction = build_transaction(record?, &config, &handlebars)?;
-        renderer.render(&transaction, &mut write)?;
+        let directives = build_transaction(record?, &config, &handlebars, headers.as_ref())?;
+        renderer.render(&directives.transaction, &mut write)?;
+        if let Some(balance) = &directives.balance {
+            renderer.render(balance, &mut write)?;
+        }
+        if let Some(price) = &directives.price {
+            renderer.render(price, &mut write)?;
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(narration: &str) -> TransactionTemplate {
+        TransactionTemplate {
+            date: "{{date}}".to_string(),
+            flag: default_transaction_flag(),
+            payee: None,
+            narration: narration.to_string(),
+            postings: Vec::new(),
+            balance: None,
+            price: None,
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            delimiter: ',',
+            quote: '"',
+            skip: 0,
+            date_format: DateFormats::Single("%Y-%m-%d".to_string()),
+            header: false,
+            trim: false,
+            flexible: false,
+            decimal_separator: '.',
+            thousands_separator: None,
+            datetime: false,
+            utc_offset_seconds: None,
+        }
+    }
+
+    #[test]
+    fn select_prefers_a_fragments_own_rule_over_the_shared_bases() {
+        let base = ConfigFragment {
+            path: None,
+            input: HashMap::new(),
+            settings: Some(settings()),
+            output: Some(template("base-output")),
+            rules: vec![Rule {
+                matcher: RuleMatcher {
+                    field: "description".into(),
+                    contains: String::new(),
+                },
+                output: template("base-rule"),
+            }],
+        };
+        let specific = ConfigFragment {
+            path: Some("bank".into()),
+            input: HashMap::new(),
+            settings: None,
+            output: None,
+            rules: vec![Rule {
+                matcher: RuleMatcher {
+                    field: "description".into(),
+                    contains: "fee".into(),
+                },
+                output: template("specific-rule"),
+            }],
+        };
+        let config_set = ConfigSet {
+            entries: vec![base, specific],
+        };
+
+        let config = config_set
+            .select(std::path::Path::new("statements/bank.csv"))
+            .unwrap();
+
+        // The base's matcher (an empty substring) would match any record, so the specific
+        // fragment's own rule must be tried first or it would never get a chance to match.
+        assert_eq!(config.rules[0].output.narration, "specific-rule");
+        assert_eq!(config.rules[1].output.narration, "base-rule");
+    }
+
+    #[test]
+    fn decimal_and_currency_parses_default_separators() {
+        let (value, currency) = parse_decimal_and_currency("12.34 USD", &settings()).unwrap();
+        assert_eq!(value.to_string(), "12.34");
+        assert_eq!(currency, "USD");
+    }
+
+    #[test]
+    fn decimal_and_currency_parses_configured_separators() {
+        let euro_settings = Settings {
+            decimal_separator: ',',
+            thousands_separator: Some('.'),
+            ..settings()
+        };
+        let (value, currency) =
+            parse_decimal_and_currency("1.234,56 EUR", &euro_settings).unwrap();
+        assert_eq!(value.to_string(), "1234.56");
+        assert_eq!(currency, "EUR");
+    }
+
+    #[test]
+    fn select_picks_the_longest_matching_path() {
+        let generic = ConfigFragment {
+            path: Some("bank".into()),
+            input: HashMap::new(),
+            settings: Some(settings()),
+            output: Some(template("generic")),
+            rules: Vec::new(),
+        };
+        let specific = ConfigFragment {
+            path: Some("bank/checking".into()),
+            input: HashMap::new(),
+            settings: Some(settings()),
+            output: Some(template("specific")),
+            rules: Vec::new(),
+        };
+        let config_set = ConfigSet {
+            entries: vec![generic, specific],
+        };
+
+        let config = config_set
+            .select(std::path::Path::new("statements/bank/checking.csv"))
+            .unwrap();
+
+        assert_eq!(config.output.unwrap().narration, "specific");
+    }
+
+    #[test]
+    fn parse_ledger_date_falls_back_through_formats() {
+        let multi = Settings {
+            date_format: DateFormats::Multiple(vec!["%Y-%m-%d".into(), "%d/%m/%Y".into()]),
+            ..settings()
+        };
+        assert_eq!(
+            parse_ledger_date("31/01/2024", &multi).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_ledger_date_converts_timestamp_to_utc_offset() {
+        let tz_aware = Settings {
+            datetime: true,
+            date_format: DateFormats::Single("%Y-%m-%dT%H:%M:%S%z".into()),
+            utc_offset_seconds: Some(-5 * 3600),
+            ..settings()
+        };
+        // 02:00 UTC on Feb 1st is 21:00 on Jan 31st at UTC-5: the offset conversion must cross
+        // the day boundary, or this would wrongly come out as Feb 1st.
+        assert_eq!(
+            parse_ledger_date("2024-02-01T02:00:00+0000", &tz_aware).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_ledger_date_rejects_out_of_range_utc_offset() {
+        let bad_offset = Settings {
+            datetime: true,
+            date_format: DateFormats::Single("%Y-%m-%dT%H:%M:%S%z".into()),
+            utc_offset_seconds: Some(100_000),
+            ..settings()
+        };
+        let error = parse_ledger_date("2024-01-31T23:30:00+0000", &bad_offset).unwrap_err();
+        assert!(matches!(error, TransactionError::InvalidUtcOffset(100_000)));
+    }
+
+    #[test]
+    fn settings_validate_rejects_empty_date_format() {
+        let empty = Settings {
+            date_format: DateFormats::Multiple(Vec::new()),
+            ..settings()
+        };
+        assert!(matches!(
+            empty.validate().unwrap_err(),
+            SettingsError::EmptyDateFormat
+        ));
+    }
+
+    #[test]
+    fn settings_validate_rejects_out_of_range_utc_offset() {
+        let bad_offset = Settings {
+            utc_offset_seconds: Some(-100_000),
+            ..settings()
+        };
+        assert!(matches!(
+            bad_offset.validate().unwrap_err(),
+            SettingsError::InvalidUtcOffset(-100_000)
+        ));
+    }
+
+    fn config(rules: Vec<Rule>, output: Option<TransactionTemplate>) -> Configuration {
+        Configuration {
+            input: HashMap::new(),
+            settings: settings(),
+            output,
+            rules,
+        }
+    }
+
+    #[test]
+    fn select_template_uses_the_first_rule_whose_field_contains_the_match() {
+        let config = config(
+            vec![
+                Rule {
+                    matcher: RuleMatcher {
+                        field: "description".into(),
+                        contains: "fee".into(),
+                    },
+                    output: template("fee-rule"),
+                },
+                Rule {
+                    matcher: RuleMatcher {
+                        field: "description".into(),
+                        contains: "deposit".into(),
+                    },
+                    output: template("deposit-rule"),
+                },
+            ],
+            Some(template("fallback")),
+        );
+
+        let mut data = HashMap::new();
+        data.insert("description", "monthly maintenance fee");
+        assert_eq!(
+            select_template(&config, &data).unwrap().narration,
+            "fee-rule"
+        );
+    }
+
+    #[test]
+    fn select_template_falls_back_to_output_when_no_rule_matches() {
+        let config = config(
+            vec![Rule {
+                matcher: RuleMatcher {
+                    field: "description".into(),
+                    contains: "fee".into(),
+                },
+                output: template("fee-rule"),
+            }],
+            Some(template("fallback")),
+        );
+
+        let mut data = HashMap::new();
+        data.insert("description", "monthly deposit");
+        assert_eq!(
+            select_template(&config, &data).unwrap().narration,
+            "fallback"
+        );
+    }
+
+    #[test]
+    fn select_template_errors_when_nothing_matches_and_there_is_no_output() {
+        let config = config(
+            vec![Rule {
+                matcher: RuleMatcher {
+                    field: "description".into(),
+                    contains: "fee".into(),
+                },
+                output: template("fee-rule"),
+            }],
+            None,
+        );
+
+        let mut data = HashMap::new();
+        data.insert("description", "monthly deposit");
+        assert!(matches!(
+            select_template(&config, &data).unwrap_err(),
+            TransactionError::NoMatchingRule
+        ));
+    }
+
+    #[test]
+    fn column_ref_index_resolves_regardless_of_headers() {
+        assert_eq!(ColumnRef::Index(2).resolve(None).unwrap(), 2);
+    }
+
+    #[test]
+    fn column_ref_name_resolves_against_the_header_row() {
+        let headers = csv::StringRecord::from(vec!["Date", "Description", "Amount"]);
+        assert_eq!(
+            ColumnRef::Name("Description".to_string())
+                .resolve(Some(&headers))
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn column_ref_name_errors_without_a_matching_header() {
+        let headers = csv::StringRecord::from(vec!["Date", "Amount"]);
+        assert!(matches!(
+            ColumnRef::Name("Description".to_string())
+                .resolve(Some(&headers))
+                .unwrap_err(),
+            TransactionError::UnknownColumn(name) if name == "Description"
+        ));
+    }
+
+    #[test]
+    fn column_ref_name_errors_when_there_are_no_headers_at_all() {
+        assert!(matches!(
+            ColumnRef::Name("Description".to_string())
+                .resolve(None)
+                .unwrap_err(),
+            TransactionError::UnknownColumn(name) if name == "Description"
+        ));
+    }
+
+    #[test]
+    fn csv_reader_respects_trim_and_flexible_settings() {
+        // Mirrors how `main` configures its `csv::ReaderBuilder` from `Settings`.
+        let settings = Settings {
+            trim: true,
+            flexible: true,
+            ..settings()
+        };
+        let data = "  a  , b\n  c  \n";
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(settings.delimiter as u8)
+            .quote(settings.quote as u8)
+            .has_headers(settings.header)
+            .trim(if settings.trim {
+                csv::Trim::All
+            } else {
+                csv::Trim::None
+            })
+            .flexible(settings.flexible)
+            .from_reader(data.as_bytes());
+
+        let records: Vec<csv::StringRecord> = reader.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(&records[0][0], "a");
+        assert_eq!(&records[0][1], "b");
+        assert_eq!(records[1].len(), 1);
+        assert_eq!(&records[1][0], "c");
+    }
+
+    fn handlebars_with_amount_helpers() -> Handlebars<'static> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        register_amount_helpers(&mut handlebars);
+        handlebars
+    }
+
+    #[test]
+    fn abs_and_neg_helpers_operate_on_decimal_strings() {
+        let handlebars = handlebars_with_amount_helpers();
+        let mut data = HashMap::new();
+        data.insert("amount", "-5.00");
+        assert_eq!(
+            handlebars.render_template("{{abs amount}}", &data).unwrap(),
+            "5.00"
+        );
+        assert_eq!(
+            handlebars.render_template("{{neg amount}}", &data).unwrap(),
+            "5.00"
+        );
+    }
+
+    #[test]
+    fn gt_and_lt_helpers_accept_an_unquoted_numeric_literal() {
+        // `0` here is parsed by handlebars as a JSON number, not a string, unlike `amount` which
+        // always arrives as a rendered csv field.
+        let handlebars = handlebars_with_amount_helpers();
+        let mut data = HashMap::new();
+        data.insert("amount", "5.00");
+        assert_eq!(
+            handlebars
+                .render_template("{{#if (gt amount 0)}}positive{{else}}other{{/if}}", &data)
+                .unwrap(),
+            "positive"
+        );
+
+        data.insert("amount", "-5.00");
+        assert_eq!(
+            handlebars
+                .render_template("{{#if (lt amount 0)}}negative{{else}}other{{/if}}", &data)
+                .unwrap(),
+            "negative"
+        );
+    }
+
+    fn render_transaction(transaction: &Transaction<'_>) -> String {
+        let mut buf = Vec::new();
+        BasicRenderer::default()
+            .render(transaction, &mut buf)
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    fn single_posting_transaction(posting: Posting<'_>) -> Transaction<'_> {
+        Transaction::builder()
+            .date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().into())
+            .flag(Flag::from(default_transaction_flag()))
+            .payee(None)
+            .narration(Cow::from("test"))
+            .postings(vec![posting])
+            .build()
+    }
+
+    #[test]
+    fn build_posting_uses_when_positive_account_for_a_positive_amount() {
+        let handlebars = handlebars_with_amount_helpers();
+        let mut data = HashMap::new();
+        data.insert("amount", "5.00 USD");
+        let posting_template = YamlPosting {
+            flag: None,
+            account: "Assets:Checking".to_string(),
+            amount: Some("{{amount}}".to_string()),
+            cost: None,
+            price: None,
+            when_positive: Some("Income:Unexpected".to_string()),
+            when_negative: Some("Expenses:Fees".to_string()),
+            negate: false,
+        };
+
+        let posting = build_posting(&posting_template, &handlebars, &data, &settings()).unwrap();
+        let rendered = render_transaction(&single_posting_transaction(posting));
+
+        assert!(rendered.contains("Income:Unexpected"));
+        assert!(!rendered.contains("Assets:Checking"));
+        assert!(!rendered.contains("Expenses:Fees"));
+    }
+
+    #[test]
+    fn build_posting_uses_when_negative_account_for_a_negative_amount() {
+        let handlebars = handlebars_with_amount_helpers();
+        let mut data = HashMap::new();
+        data.insert("amount", "-5.00 USD");
+        let posting_template = YamlPosting {
+            flag: None,
+            account: "Assets:Checking".to_string(),
+            amount: Some("{{amount}}".to_string()),
+            cost: None,
+            price: None,
+            when_positive: Some("Income:Unexpected".to_string()),
+            when_negative: Some("Expenses:Fees".to_string()),
+            negate: false,
+        };
+
+        let posting = build_posting(&posting_template, &handlebars, &data, &settings()).unwrap();
+        let rendered = render_transaction(&single_posting_transaction(posting));
+
+        assert!(rendered.contains("Expenses:Fees"));
+        assert!(!rendered.contains("Assets:Checking"));
+        assert!(!rendered.contains("Income:Unexpected"));
+    }
+
+    #[test]
+    fn build_posting_falls_back_to_account_without_when_positive_or_when_negative() {
+        let handlebars = handlebars_with_amount_helpers();
+        let mut data = HashMap::new();
+        data.insert("amount", "5.00 USD");
+        let posting_template = YamlPosting {
+            flag: None,
+            account: "Assets:Checking".to_string(),
+            amount: Some("{{amount}}".to_string()),
+            cost: None,
+            price: None,
+            when_positive: None,
+            when_negative: None,
+            negate: false,
+        };
+
+        let posting = build_posting(&posting_template, &handlebars, &data, &settings()).unwrap();
+        let rendered = render_transaction(&single_posting_transaction(posting));
+
+        assert!(rendered.contains("Assets:Checking"));
+    }
+
+    #[test]
+    fn build_balance_skips_the_directive_when_the_rendered_amount_is_empty() {
+        let handlebars = Handlebars::new();
+        let mut data = HashMap::new();
+        data.insert("amount", "");
+        let balance_template = BalanceTemplate {
+            account: "Assets:Checking".to_string(),
+            amount: "{{amount}}".to_string(),
+            date: None,
+        };
+
+        let balance =
+            build_balance(&balance_template, "{{date}}", &handlebars, &data, &settings()).unwrap();
+        assert!(balance.is_none());
+    }
+
+    #[test]
+    fn build_balance_builds_the_directive_when_an_amount_is_present() {
+        let handlebars = Handlebars::new();
+        let mut data = HashMap::new();
+        data.insert("amount", "100.00 USD");
+        data.insert("date", "2024-01-01");
+        let balance_template = BalanceTemplate {
+            account: "Assets:Checking".to_string(),
+            amount: "{{amount}}".to_string(),
+            date: None,
+        };
+
+        let balance =
+            build_balance(&balance_template, "{{date}}", &handlebars, &data, &settings()).unwrap();
+        assert!(balance.is_some());
+    }
+
+    #[test]
+    fn build_price_skips_the_directive_when_the_rendered_amount_is_empty() {
+        let handlebars = Handlebars::new();
+        let mut data = HashMap::new();
+        data.insert("amount", "");
+        let price_template = PriceTemplate {
+            commodity: "USD".to_string(),
+            amount: "{{amount}}".to_string(),
+            date: None,
+        };
+
+        let price =
+            build_price(&price_template, "{{date}}", &handlebars, &data, &settings()).unwrap();
+        assert!(price.is_none());
+    }
+
+    #[test]
+    fn build_price_builds_the_directive_when_an_amount_is_present() {
+        let handlebars = Handlebars::new();
+        let mut data = HashMap::new();
+        data.insert("amount", "1.25 EUR");
+        data.insert("date", "2024-01-01");
+        let price_template = PriceTemplate {
+            commodity: "USD".to_string(),
+            amount: "{{amount}}".to_string(),
+            date: None,
+        };
+
+        let price =
+            build_price(&price_template, "{{date}}", &handlebars, &data, &settings()).unwrap();
+        assert!(price.is_some());
+    }
+}